@@ -0,0 +1,29 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License").
+// You may not use this file except in compliance with the License.
+// A copy of the License is located at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// or in the "license" file accompanying this file. This file is distributed
+// on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing
+// permissions and limitations under the License.
+
+extern crate failure;
+extern crate fnv;
+#[macro_use]
+extern crate lazy_static;
+extern crate rayon;
+extern crate regex;
+#[macro_use]
+extern crate serde_derive;
+
+pub mod license;
+pub mod store;
+pub mod strategy;
+
+pub use license::{LicenseType, TextData};
+pub use store::Store;
+pub use strategy::{ContainedResult, IdentifiedLicense, ScanResult, ScanStrategy};
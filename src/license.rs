@@ -0,0 +1,266 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License").
+// You may not use this file except in compliance with the License.
+// A copy of the License is located at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// or in the "license" file accompanying this file. This file is distributed
+// on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing
+// permissions and limitations under the License.
+
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use fnv::FnvHasher;
+use regex::Regex;
+
+/// How a license was originally added to a `Store`.
+#[derive(Serialize, Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LicenseType {
+    /// The canonical, unmodified text of a license.
+    Original,
+    /// A known textual variant of a license (e.g. a reflowed copy).
+    Alternate,
+}
+
+/// A tokenized, comparable view of a document.
+///
+/// `TextData` keeps the document's lines around, along with a `view` into
+/// those lines that analysis and matching operate over. Narrowing the view
+/// (via `optimize_bounds`) keeps all of the document's lines and only
+/// changes the view range, so offsets reported by `lines_view` stay
+/// meaningful relative to the original document.
+#[derive(Debug, Clone)]
+pub struct TextData {
+    lines: Vec<String>,
+    view_start: usize,
+    view_end: usize,
+}
+
+lazy_static! {
+    static ref WORD: Regex = Regex::new(r"\w+").unwrap();
+}
+
+impl TextData {
+    pub fn new(text: &str) -> TextData {
+        let lines: Vec<String> = text.lines().map(|line| line.to_string()).collect();
+        let view_end = lines.len();
+        TextData {
+            lines,
+            view_start: 0,
+            view_end,
+        }
+    }
+
+    /// The raw (non-normalized) lines of this view, in original order.
+    pub fn lines(&self) -> &[String] {
+        &self.lines[self.view_start..self.view_end]
+    }
+
+    /// The `(start, end)` token range this view covers within the original
+    /// document.
+    pub fn token_view(&self) -> (usize, usize) {
+        let start = Self::count_tokens(&self.lines[..self.view_start]);
+        let end = start + self.num_tokens();
+        (start, end)
+    }
+
+    /// The `(start, end)` byte range this view covers within the original
+    /// document, joining lines with a single newline byte and not counting
+    /// a trailing newline after the document's last line.
+    pub fn byte_view(&self) -> (usize, usize) {
+        (
+            Self::count_bytes(&self.lines, self.view_start),
+            Self::count_bytes(&self.lines, self.view_end),
+        )
+    }
+
+    /// The number of word tokens in this view.
+    pub fn num_tokens(&self) -> usize {
+        self.tokens().len()
+    }
+
+    /// A stable hash of this view's normalized tokens, suitable for keying
+    /// a content-based override table.
+    ///
+    /// This uses FNV-1a rather than `std`'s `DefaultHasher`, whose algorithm
+    /// is explicitly unspecified and may change between Rust releases. A
+    /// persisted clarifications table needs hashes that stay valid across
+    /// toolchain upgrades, so the algorithm here is pinned.
+    pub fn content_hash(&self) -> u64 {
+        let mut hasher = FnvHasher::default();
+        for token in self.tokens() {
+            token.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+
+    /// Given `window`, a view believed to match a portion of this license's
+    /// text, estimates whether the tokens the two share form a genuine
+    /// leading prefix of this license, returning a fraction in `[0.0,
+    /// 1.0]`.
+    ///
+    /// Common filler words ("of", "the", "is") recur throughout real
+    /// license text, including near its very end, so merely checking
+    /// whether one of this license's tokens shows up *somewhere* in
+    /// `window` -- or even where the *last* such token falls -- is
+    /// misleading: a genuine header full of ordinary words can appear to
+    /// "reach" all the way to the license's final line. Instead, this
+    /// finds the longest run of this license's distinct tokens, starting
+    /// at its very first token, that are all present in `window`, and
+    /// compares that run's length to the total number of this license's
+    /// distinct tokens that appear in `window` at all. A ratio near `1.0`
+    /// means essentially every token the two share is accounted for by a
+    /// contiguous leading run, as expected from a genuine header; a ratio
+    /// near `0.0` means the shared tokens are scattered elsewhere in the
+    /// text instead.
+    pub(crate) fn leading_prefix_coverage(&self, window: &TextData) -> f32 {
+        let window_tokens = window.token_set();
+
+        let mut seen = HashSet::new();
+        let mut ordered_tokens = Vec::new();
+        for token in self.tokens() {
+            if seen.insert(token.clone()) {
+                ordered_tokens.push(token);
+            }
+        }
+
+        let matched = ordered_tokens
+            .iter()
+            .filter(|token| window_tokens.contains(*token))
+            .count();
+        if matched == 0 {
+            return 0.0;
+        }
+
+        let leading_run = ordered_tokens
+            .iter()
+            .take_while(|token| window_tokens.contains(*token))
+            .count();
+
+        leading_run as f32 / matched as f32
+    }
+
+    /// The `(start, end)` line range this view covers within the original
+    /// document.
+    pub fn lines_view(&self) -> (usize, usize) {
+        (self.view_start, self.view_end)
+    }
+
+    /// Returns a new view over the same underlying lines, with the view
+    /// range replaced by `[start, end)`.
+    ///
+    /// Since line positions stay stable across `white_out`/`optimize_bounds`
+    /// passes, this lets a later pass look back at a range of the pristine
+    /// document even after its own view has been narrowed or masked.
+    pub(crate) fn with_view(&self, start: usize, end: usize) -> TextData {
+        TextData {
+            lines: self.lines.clone(),
+            view_start: start,
+            view_end: end,
+        }
+    }
+
+    /// Finds the contiguous line range within this view that best matches
+    /// `license_data`'s tokens, returning a narrowed view over that range
+    /// and its match score.
+    pub fn optimize_bounds(&self, license_data: TextData) -> (TextData, f32) {
+        let license_tokens = license_data.token_set();
+        let lines = self.lines();
+
+        let mut best_score = 0.0_f32;
+        let mut best_range = (self.view_start, self.view_start);
+
+        for start in 0..lines.len() {
+            for end in (start + 1)..=lines.len() {
+                let window = TextData {
+                    lines: self.lines.clone(),
+                    view_start: self.view_start + start,
+                    view_end: self.view_start + end,
+                };
+                let score = dice_score(&window.token_set(), &license_tokens);
+                if score > best_score {
+                    best_score = score;
+                    best_range = (window.view_start, window.view_end);
+                }
+            }
+        }
+
+        let bounded = TextData {
+            lines: self.lines.clone(),
+            view_start: best_range.0,
+            view_end: best_range.1,
+        };
+        (bounded, best_score)
+    }
+
+    /// Blanks out this view's lines within the overall document, and
+    /// returns a new, full-width `TextData` over the result so it can be
+    /// re-analyzed for further matches.
+    pub fn white_out(&self) -> Option<TextData> {
+        let mut lines = self.lines.clone();
+        for line in lines.iter_mut().take(self.view_end).skip(self.view_start) {
+            line.clear();
+        }
+        let view_end = lines.len();
+        Some(TextData {
+            lines,
+            view_start: 0,
+            view_end,
+        })
+    }
+
+    /// How similar this view is to `other`, as a Dice coefficient over
+    /// their token sets.
+    pub(crate) fn score_against(&self, other: &TextData) -> f32 {
+        dice_score(&self.token_set(), &other.token_set())
+    }
+
+    fn tokens(&self) -> Vec<String> {
+        self.lines()
+            .iter()
+            .flat_map(|line| WORD.find_iter(line).map(|m| m.as_str().to_lowercase()))
+            .collect()
+    }
+
+    fn token_set(&self) -> HashSet<String> {
+        self.tokens().into_iter().collect()
+    }
+
+    fn count_tokens(lines: &[String]) -> usize {
+        lines.iter().map(|line| WORD.find_iter(line).count()).sum()
+    }
+
+    /// Counts the bytes spanned by `lines[..at]` joined with newlines, with
+    /// no newline added after the document's last line.
+    fn count_bytes(lines: &[String], at: usize) -> usize {
+        lines[..at]
+            .iter()
+            .enumerate()
+            .map(|(i, line)| {
+                if i + 1 == lines.len() {
+                    line.len()
+                } else {
+                    line.len() + 1
+                }
+            })
+            .sum()
+    }
+}
+
+impl<'a> From<&'a str> for TextData {
+    fn from(text: &'a str) -> TextData {
+        TextData::new(text)
+    }
+}
+
+fn dice_score(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    let intersection = a.intersection(b).count();
+    (2.0 * intersection as f32) / (a.len() + b.len()) as f32
+}
@@ -0,0 +1,98 @@
+// Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License").
+// You may not use this file except in compliance with the License.
+// A copy of the License is located at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// or in the "license" file accompanying this file. This file is distributed
+// on an "AS IS" BASIS, WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either
+// express or implied. See the License for the specific language governing
+// permissions and limitations under the License.
+
+use std::collections::HashMap;
+
+use failure::Error;
+
+use license::{LicenseType, TextData};
+
+/// A single license's canonical text, kept alongside its name and type.
+struct StoredLicense {
+    license_type: LicenseType,
+    data: TextData,
+}
+
+/// A single scored match against a license in a `Store`.
+#[derive(Debug, Clone)]
+pub struct Match {
+    pub score: f32,
+    pub name: String,
+    pub license_type: LicenseType,
+    pub data: TextData,
+}
+
+/// A collection of known licenses that documents can be matched against.
+pub struct Store {
+    licenses: HashMap<String, StoredLicense>,
+}
+
+impl Default for Store {
+    fn default() -> Store {
+        Store::new()
+    }
+}
+
+impl Store {
+    pub fn new() -> Store {
+        Store {
+            licenses: HashMap::new(),
+        }
+    }
+
+    /// Adds a license's canonical text to the store under `name`.
+    pub fn add_license(&mut self, name: String, text: String) {
+        self.licenses.insert(
+            name,
+            StoredLicense {
+                license_type: LicenseType::Original,
+                data: TextData::new(&text),
+            },
+        );
+    }
+
+    /// Finds the single best-scoring license in the store for `text`.
+    ///
+    /// Panics if the store has no licenses loaded, since there's nothing to
+    /// compare against.
+    pub fn analyze(&self, text: &TextData) -> Result<Match, Error> {
+        Ok(self
+            .analyze_candidates(text)?
+            .into_iter()
+            .next()
+            .expect("store has no licenses loaded to analyze against"))
+    }
+
+    /// Scores `text` against every license in the store, returning all
+    /// candidates sorted by descending score.
+    pub fn analyze_candidates(&self, text: &TextData) -> Result<Vec<Match>, Error> {
+        let mut matches: Vec<Match> = self
+            .licenses
+            .iter()
+            .map(|(name, stored)| Match {
+                score: text.score_against(&stored.data),
+                name: name.clone(),
+                license_type: stored.license_type,
+                data: stored.data.clone(),
+            })
+            .collect();
+
+        matches.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(::std::cmp::Ordering::Equal)
+        });
+
+        Ok(matches)
+    }
+}
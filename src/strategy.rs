@@ -15,17 +15,70 @@
 /// This module is experimental. Its API should not be considered stable
 /// in any form.
 use std::borrow::Cow;
+use std::collections::HashMap;
 
 use failure::Error;
+use rayon::prelude::*;
+use regex::Regex;
 
 use license::LicenseType;
 use license::TextData;
-use store::Store;
+use store::{Match, Store};
 
-#[derive(Serialize, Debug)]
+#[derive(Serialize, Debug, Clone)]
 pub struct IdentifiedLicense {
     pub name: String,
     pub kind: LicenseType,
+    pub match_kind: MatchKind,
+}
+
+/// How much of a license's canonical text a match actually covers.
+///
+/// A `scan` may turn up a file that contains the full text of a license, or
+/// one that merely references it in a header comment or notice. `MatchKind`
+/// lets callers tell these apart instead of treating every match as a full
+/// reproduction of the license.
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+pub enum MatchKind {
+    /// The match covers (most of) the license's full canonical text.
+    FullText,
+    /// The match covers only a small leading prefix of the license's text,
+    /// as is typical of a source file header.
+    Header,
+    /// The match covers a very small fragment of the license, such as a
+    /// one-line notice referencing it by name.
+    Notice,
+}
+
+/// Classifies a match based on how many tokens of the license's canonical
+/// text were actually covered, out of its total token count, and -- for
+/// `Header` specifically -- whether those tokens stay within the license's
+/// leading portion rather than being drawn from its middle or end.
+///
+/// `leading_prefix_coverage` is how much of the shared tokens (see
+/// `TextData::leading_prefix_coverage`) are accounted for by a contiguous
+/// leading run of the license's text; a small match that isn't confined
+/// to a leading prefix is a `Notice`-like fragment quoted from elsewhere
+/// in the text, not a source file header.
+fn classify_match(
+    matched_tokens: usize,
+    canonical_tokens: usize,
+    leading_prefix_coverage: f32,
+) -> MatchKind {
+    if canonical_tokens == 0 {
+        return MatchKind::FullText;
+    }
+
+    let coverage = matched_tokens as f32 / canonical_tokens as f32;
+    if coverage >= 0.4 {
+        return MatchKind::FullText;
+    }
+
+    if coverage >= 0.15 && leading_prefix_coverage >= 0.6 {
+        MatchKind::Header
+    } else {
+        MatchKind::Notice
+    }
 }
 
 #[derive(Serialize, Debug)]
@@ -33,13 +86,170 @@ pub struct ScanResult {
     pub score: f32,
     pub license: Option<IdentifiedLicense>,
     pub containing: Vec<ContainedResult>,
+    pub attribution: Option<String>,
+    pub confidence: Confidence,
 }
 
-#[derive(Serialize, Debug)]
+impl ScanResult {
+    /// Builds a combined SPDX license expression out of this result's
+    /// contained matches, joining the distinct identifiers with `AND` in
+    /// sorted order.
+    ///
+    /// Only contained matches whose `match_kind` is `MatchKind::FullText`
+    /// and whose name looks like a valid SPDX short identifier are
+    /// considered -- a `Header` or `Notice` match only means the file
+    /// references that license, not that it's actually under it. If none
+    /// qualify, this falls back to the top-level `license` name alone.
+    pub fn spdx_expression(&self) -> Option<String> {
+        lazy_static! {
+            static ref SPDX_ID: Regex = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9.+-]*$").unwrap();
+        }
+
+        let mut names: Vec<&str> = self
+            .containing
+            .iter()
+            .filter(|contained| contained.license.match_kind == MatchKind::FullText)
+            .map(|contained| contained.license.name.as_str())
+            .filter(|name| SPDX_ID.is_match(name))
+            .collect();
+        names.sort_unstable();
+        names.dedup();
+
+        if names.is_empty() {
+            return self.license.as_ref().map(|license| license.name.clone());
+        }
+
+        Some(names.join(" AND "))
+    }
+}
+
+#[derive(Serialize, Debug, Clone)]
 pub struct ContainedResult {
     pub score: f32,
     pub license: IdentifiedLicense,
     pub line_range: (usize, usize),
+    pub token_range: (usize, usize),
+    pub byte_range: (usize, usize),
+    pub attribution: Option<String>,
+    pub confidence: Confidence,
+}
+
+/// A semantic take on a result's score, relative to a `ScanStrategy`'s
+/// configured thresholds, so callers don't each have to pick their own
+/// magic score bands.
+#[derive(Serialize, Debug, Copy, Clone, PartialEq)]
+pub enum Confidence {
+    /// Score is above the shallow limit -- about as sure as askalono gets.
+    Confident,
+    /// Score is above the confidence threshold, but below the shallow limit.
+    SemiConfident,
+    /// Score is below the confidence threshold.
+    Unsure,
+    /// No candidate license scored above zero.
+    NoMatch,
+    /// More than one stored license scored within a narrow band of the top
+    /// match; the result is ambiguous and likely needs human review.
+    MultiplePossible,
+}
+
+/// Licenses scoring within this margin of the top match are considered
+/// close enough to make a result ambiguous.
+const MULTIPLE_MATCH_MARGIN: f32 = 0.02;
+
+fn classify_confidence(score: f32, confidence_threshold: f32, shallow_limit: f32) -> Confidence {
+    if score > shallow_limit {
+        Confidence::Confident
+    } else if score > confidence_threshold {
+        Confidence::SemiConfident
+    } else if score > 0.0 {
+        Confidence::Unsure
+    } else {
+        Confidence::NoMatch
+    }
+}
+
+/// Builds a lowercase word-frequency map out of a blob of text, for use by
+/// the frequency fallback scorer.
+fn word_frequencies(text: &str) -> HashMap<String, usize> {
+    lazy_static! {
+        static ref WORD: Regex = Regex::new(r"\w+").unwrap();
+    }
+
+    let mut freq = HashMap::new();
+    for word in WORD.find_iter(text) {
+        *freq.entry(word.as_str().to_lowercase()).or_insert(0) += 1;
+    }
+    freq
+}
+
+/// Scores a word-frequency map against a canonical one: the total absolute
+/// difference in word counts, relative to the canonical text's total word
+/// count, turned into a confidence in `[0, 1]`.
+fn frequency_score(
+    text_freq: &HashMap<String, usize>,
+    canonical_freq: &HashMap<String, usize>,
+) -> f32 {
+    let total_template_words: usize = canonical_freq.values().sum();
+    if total_template_words == 0 {
+        return 0.0;
+    }
+
+    let errors: usize = canonical_freq
+        .iter()
+        .map(|(word, &template_count)| {
+            let text_count = text_freq.get(word).cloned().unwrap_or(0);
+            (text_count as isize - template_count as isize).unsigned_abs()
+        })
+        .sum();
+
+    (1.0 - (errors as f32 / total_template_words as f32)).max(0.0)
+}
+
+/// Looks for a copyright/attribution statement in the original (i.e.
+/// non-normalized) lines of a `TextData`, and returns it as a single
+/// string if one is found.
+///
+/// This scans for one or more consecutive lines that look like a copyright
+/// notice -- starting with "copyright", "(c)" or "©" and containing a
+/// four-digit year -- and joins them with the following lines as long as
+/// those continuation lines are indented or themselves begin with "(c)".
+/// Trailing "All rights reserved." boilerplate is stripped from the
+/// result when it trails the copyright statement on the same line, or an
+/// indented continuation of it. A bare, left-aligned "All rights
+/// reserved." line -- the common two-line form -- isn't a continuation
+/// line at all, so it's simply left out of the collected attribution
+/// instead of being stripped out of it; either way, it doesn't end up in
+/// the result.
+fn extract_attribution(text: &TextData) -> Option<String> {
+    lazy_static! {
+        static ref COPYRIGHT_LINE: Regex =
+            Regex::new(r"(?i)^\s*(copyright|\(c\)|©)(\s|\b).*\d{4}.*$").unwrap();
+        static ref ALL_RIGHTS_RESERVED: Regex =
+            Regex::new(r"(?i)\s*all rights reserved\.?\s*$").unwrap();
+    }
+
+    let mut lines = text.lines().iter();
+    let first = lines.find(|line| COPYRIGHT_LINE.is_match(line))?;
+
+    let mut collected = vec![first.trim().to_string()];
+    for line in lines {
+        let trimmed = line.trim_start();
+        if trimmed.is_empty() {
+            break;
+        }
+        if line.starts_with(char::is_whitespace)
+            || trimmed.to_lowercase().starts_with("(c)")
+            || COPYRIGHT_LINE.is_match(line)
+        {
+            collected.push(trimmed.trim_end().to_string());
+        } else {
+            break;
+        }
+    }
+
+    let joined = collected.join(" ");
+    let stripped = ALL_RIGHTS_RESERVED.replace(&joined, "");
+    Some(stripped.trim().to_string())
 }
 
 /// A `ScanStrategy` can be used as a high-level wrapped over a `Store`'s
@@ -71,8 +281,14 @@ pub struct ScanStrategy<'a> {
     shallow_limit: f32,
     optimize: bool,
     max_passes: u16,
+    frequency_fallback: bool,
+    clarifications: HashMap<u64, IdentifiedLicense>,
 }
 
+/// How far below `confidence_threshold` a primary score may fall and still
+/// be eligible for the frequency fallback scorer.
+const FREQUENCY_FALLBACK_MARGIN: f32 = 0.2;
+
 impl<'a> ScanStrategy<'a> {
     pub fn new(store: &'a Store) -> ScanStrategy<'a> {
         Self {
@@ -81,6 +297,8 @@ impl<'a> ScanStrategy<'a> {
             shallow_limit: 0.99,
             optimize: false,
             max_passes: 10,
+            frequency_fallback: false,
+            clarifications: HashMap::new(),
         }
     }
 
@@ -104,17 +322,159 @@ impl<'a> ScanStrategy<'a> {
         self
     }
 
+    /// Enables a secondary, word-frequency-based scorer that kicks in when
+    /// the primary analysis falls just short of `confidence_threshold`.
+    ///
+    /// This catches heavily reordered or reformatted license texts that the
+    /// primary n-gram/set similarity undercounts, at the cost of a second
+    /// pass over near-miss documents.
+    pub fn enable_frequency_fallback(mut self, enable: bool) -> Self {
+        self.frequency_fallback = enable;
+        self
+    }
+
+    /// Pins specific documents to a known license, keyed by the content
+    /// hash of their normalized `TextData`, bypassing fuzzy detection
+    /// entirely for those hashes.
+    ///
+    /// This is meant for vendored files whose detection is known-wrong and
+    /// have been clarified by hand, mirroring the "clarifications" concept
+    /// other license scanners expose for the same problem.
+    pub fn clarifications(mut self, clarifications: HashMap<u64, IdentifiedLicense>) -> Self {
+        self.clarifications = clarifications;
+        self
+    }
+
+    /// Scans many documents at once, running the analysis for each one in
+    /// parallel across a rayon thread pool.
+    ///
+    /// Since `Store` analysis is read-only, the same strategy can be shared
+    /// across threads as-is; this is simply a parallel `map` over
+    /// [`scan`](#method.scan) that preserves the input order in its output.
+    pub fn scan_many(&self, texts: &[TextData]) -> Vec<Result<ScanResult, Error>> {
+        texts.par_iter().map(|text| self.scan(text)).collect()
+    }
+
+    /// Checks whether more than one of `candidates` scored within
+    /// `MULTIPLE_MATCH_MARGIN` of `top_score`, meaning the match is
+    /// ambiguous.
+    ///
+    /// Takes an already-scored candidate list rather than a `TextData` so
+    /// callers that already have one on hand (e.g. from `Store::analyze_candidates`)
+    /// don't pay for a second full pass over the store just to check for
+    /// competitors.
+    fn has_close_competitor(candidates: &[Match], top_score: f32) -> bool {
+        candidates
+            .iter()
+            .filter(|candidate| top_score - candidate.score <= MULTIPLE_MATCH_MARGIN)
+            .count()
+            > 1
+    }
+
+    fn confidence_for(&self, candidates: &[Match], score: f32) -> Confidence {
+        let confidence = classify_confidence(score, self.confidence_threshold, self.shallow_limit);
+        if confidence != Confidence::NoMatch && Self::has_close_competitor(candidates, score) {
+            return Confidence::MultiplePossible;
+        }
+        confidence
+    }
+
+    /// Scores `text` against every stored license using the word-frequency
+    /// scorer, pairing each candidate with its frequency score.
+    ///
+    /// Computed once per `scan()` call so both the fallback match and its
+    /// ambiguity check (`has_close_frequency_competitor`) can reuse it
+    /// instead of each re-running the frequency scorer over the whole
+    /// store.
+    fn frequency_candidates(&self, text: &TextData) -> Result<Vec<(Match, f32)>, Error> {
+        let candidates = self.store.analyze_candidates(text)?;
+        let text_freq = word_frequencies(&text.lines().join("\n"));
+
+        Ok(candidates
+            .into_iter()
+            .map(|candidate| {
+                let canonical_freq = word_frequencies(&candidate.data.lines().join("\n"));
+                let freq_score = frequency_score(&text_freq, &canonical_freq);
+                (candidate, freq_score)
+            })
+            .collect())
+    }
+
+    /// Like `has_close_competitor`, but over frequency-scored candidates,
+    /// so a frequency-rescued `top_score` is only compared against
+    /// competitor scores on the same scale.
+    fn has_close_frequency_competitor(scored: &[(Match, f32)], top_score: f32) -> bool {
+        scored
+            .iter()
+            .filter(|(_, freq_score)| top_score - freq_score <= MULTIPLE_MATCH_MARGIN)
+            .count()
+            > 1
+    }
+
+    /// Like `confidence_for`, but for a score produced by the frequency
+    /// fallback scorer, so the ambiguity check stays on the same scale as
+    /// `score` instead of mixing it with the primary dice-similarity one.
+    fn confidence_for_frequency(&self, scored: &[(Match, f32)], score: f32) -> Confidence {
+        let confidence = classify_confidence(score, self.confidence_threshold, self.shallow_limit);
+        if confidence != Confidence::NoMatch && Self::has_close_frequency_competitor(scored, score) {
+            return Confidence::MultiplePossible;
+        }
+        confidence
+    }
+
+    /// Picks the best of `scored` whose primary score came within
+    /// `FREQUENCY_FALLBACK_MARGIN` of `confidence_threshold` -- a license
+    /// far from the threshold on the primary scorer isn't worth rescuing
+    /// even if its frequency score looks good.
+    fn best_frequency_candidate(&self, scored: &[(Match, f32)]) -> Option<(String, LicenseType, f32)> {
+        let mut best: Option<(String, LicenseType, f32)> = None;
+        for (candidate, freq_score) in scored {
+            if self.confidence_threshold - candidate.score > FREQUENCY_FALLBACK_MARGIN {
+                continue;
+            }
+
+            let is_better = best
+                .as_ref()
+                .is_none_or(|&(_, _, best_score)| *freq_score > best_score);
+            if is_better {
+                best = Some((candidate.name.clone(), candidate.license_type, *freq_score));
+            }
+        }
+        best
+    }
+
     pub fn scan(&self, text: &TextData) -> Result<ScanResult, Error> {
-        let mut analysis = self.store.analyze(text)?;
-        let score = analysis.score;
+        // a clarified hash short-circuits detection entirely
+        if let Some(clarified) = self.clarifications.get(&text.content_hash()) {
+            return Ok(ScanResult {
+                score: 1.0,
+                license: Some(clarified.clone()),
+                containing: Vec::new(),
+                attribution: extract_attribution(text),
+                confidence: Confidence::Confident,
+            });
+        }
+
+        // score against every stored license once, and reuse that list for
+        // both the best match and its ambiguity check below, rather than
+        // running a second full pass over the store to answer the latter
+        let candidates = self.store.analyze_candidates(text)?;
+        let mut analysis = candidates
+            .first()
+            .cloned()
+            .expect("store has no licenses loaded to analyze against");
+        let mut score = analysis.score;
         let mut license = None;
         let mut containing = Vec::new();
+        let attribution = extract_attribution(text);
+        let mut confidence = self.confidence_for(&candidates, score);
 
         // meets confidence threshold? record that
         if analysis.score > self.confidence_threshold {
             license = Some(IdentifiedLicense {
                 name: analysis.name.clone(),
                 kind: analysis.license_type,
+                match_kind: MatchKind::FullText,
             });
 
             // above the shallow limit -> exit
@@ -123,30 +483,72 @@ impl<'a> ScanStrategy<'a> {
                     score,
                     license,
                     containing,
+                    attribution,
+                    confidence,
                 });
             }
         }
 
+        // still no confident match? try the opt-in frequency fallback
+        if license.is_none() && self.frequency_fallback {
+            let scored = self.frequency_candidates(text)?;
+            if let Some((name, kind, freq_score)) = self.best_frequency_candidate(&scored) {
+                if freq_score > self.confidence_threshold {
+                    score = freq_score;
+                    confidence = self.confidence_for_frequency(&scored, freq_score);
+                    license = Some(IdentifiedLicense {
+                        name,
+                        kind,
+                        match_kind: MatchKind::FullText,
+                    });
+                }
+            }
+        }
+
         if self.optimize {
             // repeatedly try to dig deeper
             // this loop effectively iterates once for each license it finds
             let mut current_text: Cow<TextData> = Cow::Borrowed(text);
             for _n in 0..self.max_passes {
-                let (optimized, optimized_score) = current_text.optimize_bounds(analysis.data);
+                let canonical_tokens = analysis.data.num_tokens();
+                let (optimized, optimized_score) =
+                    current_text.optimize_bounds(analysis.data.clone());
 
                 // stop if we didn't find anything acceptable
                 if optimized_score < self.confidence_threshold {
                     break;
                 }
 
+                // look for attribution in the pristine document, up through
+                // the end of this match -- the optimized view itself is
+                // narrowed to just the matching lines, which typically
+                // excludes the copyright notice sitting just above them
+                let (view_start, view_end) = optimized.lines_view();
+                let attribution_source = text.with_view(0, view_end);
+
+                // token and byte offsets must also come from the pristine
+                // document, since earlier passes blanked out their matches
+                let pristine_match = text.with_view(view_start, view_end);
+
+                let optimized_candidates = self.store.analyze_candidates(&optimized)?;
+
                 // otherwise, save it
                 containing.push(ContainedResult {
                     score: optimized_score,
                     license: IdentifiedLicense {
                         name: analysis.name,
                         kind: analysis.license_type,
+                        match_kind: classify_match(
+                            optimized.num_tokens(),
+                            canonical_tokens,
+                            analysis.data.leading_prefix_coverage(&optimized),
+                        ),
                     },
                     line_range: optimized.lines_view(),
+                    token_range: pristine_match.token_view(),
+                    byte_range: pristine_match.byte_view(),
+                    attribution: extract_attribution(&attribution_source),
+                    confidence: self.confidence_for(&optimized_candidates, optimized_score),
                 });
 
                 // and white-out + reanalyze for next iteration
@@ -159,6 +561,8 @@ impl<'a> ScanStrategy<'a> {
             score,
             license,
             containing,
+            attribution,
+            confidence,
         })
     }
 }
@@ -178,6 +582,229 @@ mod tests {
             .max_passes(100);
     }
 
+    #[test]
+    fn attribution_extraction() {
+        let symbolic = TextData::new(
+            "(c) 2020 Example Corp.\nAll rights reserved.\n\nPermission is hereby granted...",
+        );
+        assert_eq!(
+            extract_attribution(&symbolic),
+            Some("(c) 2020 Example Corp.".to_string())
+        );
+
+        let unicode = TextData::new("© 2020 Example Corp.\nsome license body");
+        assert_eq!(
+            extract_attribution(&unicode),
+            Some("© 2020 Example Corp.".to_string())
+        );
+
+        let spelled_out =
+            TextData::new("Copyright 2020 Example Corp.\n(C) continuation line\nsome license body");
+        assert_eq!(
+            extract_attribution(&spelled_out),
+            Some("Copyright 2020 Example Corp. (C) continuation line".to_string())
+        );
+
+        let lowercase_continuation =
+            TextData::new("Copyright 2020 Example Corp.\n(c) still part of it\nsome license body");
+        assert_eq!(
+            extract_attribution(&lowercase_continuation),
+            Some("Copyright 2020 Example Corp. (c) still part of it".to_string())
+        );
+
+        let none = TextData::new("just some text\nwith no attribution at all");
+        assert_eq!(extract_attribution(&none), None);
+
+        // the common real-world two-line layout: a bare, left-aligned "All
+        // rights reserved." isn't a continuation line, so it's excluded
+        // from the collected attribution rather than stripped out of it --
+        // but the result is the same either way
+        let two_line = TextData::new(
+            "Copyright (c) 2020 Example Corp.\nAll rights reserved.\n\nsome license body",
+        );
+        assert_eq!(
+            extract_attribution(&two_line),
+            Some("Copyright (c) 2020 Example Corp.".to_string())
+        );
+
+        // here the boilerplate *is* a continuation line (it's indented),
+        // so it's actually collected and then stripped by the
+        // ALL_RIGHTS_RESERVED regex rather than merely excluded
+        let indented_continuation = TextData::new(
+            "Copyright (c) 2020 Example Corp.\n    All rights reserved.\n\nsome license body",
+        );
+        assert_eq!(
+            extract_attribution(&indented_continuation),
+            Some("Copyright (c) 2020 Example Corp.".to_string())
+        );
+    }
+
+    #[test]
+    fn contained_match_carries_attribution_above_the_matched_span() {
+        let store = create_dummy_store();
+        let test_data = TextData::new(
+            "Copyright 2020 Example Corp.\n\naaaaa\nbbbbb\nccccc\n\nsome trailing junk that isn't part of the license",
+        );
+
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .optimize(true)
+            .shallow_limit(1.0);
+        let result = strategy.scan(&test_data).unwrap();
+        let contained = &result.containing[0];
+        assert_eq!(
+            contained.attribution,
+            Some("Copyright 2020 Example Corp.".to_string())
+        );
+    }
+
+    #[test]
+    fn match_kind_reflects_license_coverage() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-3".into(),
+            "alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu xi omicron pi rho sigma tau upsilon".into(),
+        );
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.15)
+            .optimize(true)
+            .shallow_limit(1.0);
+
+        // a one-line notice referencing the license covers only a sliver of
+        // its tokens
+        let notice = TextData::new("alpha beta\nzzz yyy xxx www vvv uuu ttt sss rrr qqq");
+        let result = strategy.scan(&notice).unwrap();
+        assert_eq!(result.containing[0].license.match_kind, MatchKind::Notice);
+
+        // a header reproduces a bit more, but still nowhere near the whole
+        // license
+        let header =
+            TextData::new("alpha beta gamma delta epsilon zeta\nzzz yyy xxx www vvv uuu ttt sss rrr qqq");
+        let result = strategy.scan(&header).unwrap();
+        assert_eq!(result.containing[0].license.match_kind, MatchKind::Header);
+
+        // the full canonical text is, unsurprisingly, a full-text match
+        let full = TextData::new(
+            "alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu xi omicron pi rho sigma tau upsilon",
+        );
+        let result = strategy.scan(&full).unwrap();
+        assert_eq!(result.containing[0].license.match_kind, MatchKind::FullText);
+    }
+
+    #[test]
+    fn header_classification_requires_a_leading_prefix_not_just_small_size() {
+        let mut store = Store::new();
+        store.add_license(
+            "license-4".into(),
+            "alpha beta gamma delta epsilon zeta eta theta iota kappa lambda mu nu xi omicron pi rho sigma tau upsilon".into(),
+        );
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.15)
+            .optimize(true)
+            .shallow_limit(1.0);
+
+        // this covers about as many tokens as the genuine header case in
+        // `match_kind_reflects_license_coverage`, but they're lifted from
+        // the middle of the license rather than its start, so it shouldn't
+        // be mistaken for a source file header
+        let mid_quote =
+            TextData::new("kappa lambda mu nu xi omicron\nzzz yyy xxx www vvv uuu ttt sss rrr qqq");
+        let result = strategy.scan(&mid_quote).unwrap();
+        assert_eq!(result.containing[0].license.match_kind, MatchKind::Notice);
+    }
+
+    #[test]
+    fn header_classification_survives_realistic_filler_words() {
+        // unlike the alpha/beta/gamma fixtures above, a real license is
+        // mostly ordinary, repeating words ("of", "the", "to", "is", ...)
+        // that show up near its end just as often as near its start --
+        // exactly the case that defeats a "does this token appear
+        // anywhere, and how late" check
+        let mit_text = concat!(
+            "Permission is hereby granted, free of charge, to any person obtaining a copy ",
+            "of this software and associated documentation files (the \"Software\"), to deal ",
+            "in the Software without restriction, including without limitation the rights to ",
+            "use, copy, modify, merge, publish, distribute, sublicense, and/or sell copies of ",
+            "the Software, and to permit persons to whom the Software is furnished to do so, ",
+            "subject to the following conditions:\n\n",
+            "The above copyright notice and this permission notice shall be included in all ",
+            "copies or substantial portions of the Software.\n\n",
+            "THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR ",
+            "IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS ",
+            "FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT. IN NO EVENT SHALL THE AUTHORS OR ",
+            "COPYRIGHT HOLDERS BE LIABLE FOR ANY CLAIM, DAMAGES OR OTHER LIABILITY, WHETHER IN ",
+            "AN ACTION OF CONTRACT, TORT OR OTHERWISE, ARISING FROM, OUT OF OR IN CONNECTION ",
+            "WITH THE SOFTWARE OR THE USE OR OTHER DEALINGS IN THE SOFTWARE."
+        );
+
+        let mut store = Store::new();
+        store.add_license("mit-like".into(), mit_text.into());
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.15)
+            .optimize(true)
+            .shallow_limit(1.0);
+
+        // a textbook header: the license's literal opening sentence,
+        // verbatim, as a source file comment would quote it
+        let header = TextData::new(
+            "Permission is hereby granted, free of charge, to any person obtaining a copy \
+             of this software and associated documentation files (the \"Software\"), to deal \
+             in the Software without restriction,\nzzz yyy xxx www vvv uuu ttt sss rrr qqq",
+        );
+        let result = strategy.scan(&header).unwrap();
+        assert_eq!(result.containing[0].license.match_kind, MatchKind::Header);
+
+        // a quote of the license's closing paragraph is just as full of
+        // common words as the header above, but it isn't a leading
+        // prefix, so it must not be mistaken for one
+        let closing = TextData::new(
+            "THE SOFTWARE IS PROVIDED \"AS IS\", WITHOUT WARRANTY OF ANY KIND, EXPRESS OR \
+             IMPLIED, INCLUDING BUT NOT LIMITED TO THE WARRANTIES OF MERCHANTABILITY, FITNESS \
+             FOR A PARTICULAR PURPOSE AND NONINFRINGEMENT.\nzzz yyy xxx www vvv uuu ttt sss rrr qqq",
+        );
+        let result = strategy.scan(&closing).unwrap();
+        assert_eq!(result.containing[0].license.match_kind, MatchKind::Notice);
+    }
+
+    #[test]
+    fn scan_many_preserves_order() {
+        let store = create_dummy_store();
+        let strategy = ScanStrategy::new(&store).confidence_threshold(0.5);
+
+        let texts = vec![
+            TextData::new("aaaaa\nbbbbb\nccccc"),
+            TextData::new("no license here at all"),
+            TextData::new("1234 5678 1234\n0000\n1010101010\n\n8888 9999"),
+        ];
+        let results = strategy.scan_many(&texts);
+
+        assert_eq!(results.len(), 3);
+        assert_eq!(
+            results[0].as_ref().unwrap().license.as_ref().unwrap().name,
+            "license-1"
+        );
+        assert!(results[1].as_ref().unwrap().license.is_none());
+        assert_eq!(
+            results[2].as_ref().unwrap().license.as_ref().unwrap().name,
+            "license-2"
+        );
+    }
+
+    #[test]
+    fn multiple_possible_when_scores_are_close() {
+        let mut store = Store::new();
+        // two distinctly-named licenses with identical canonical text will
+        // always score identically against any document, making every
+        // match between them ambiguous
+        store.add_license("dup-a".into(), "foo bar baz qux quux".into());
+        store.add_license("dup-b".into(), "foo bar baz qux quux".into());
+
+        let strategy = ScanStrategy::new(&store).confidence_threshold(0.5);
+        let test_data = TextData::new("foo bar baz qux quux");
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!(result.confidence, Confidence::MultiplePossible);
+    }
+
     #[test]
     fn shallow_scan() {
         let store = create_dummy_store();
@@ -190,7 +817,8 @@ mod tests {
         let result = strategy.scan(&test_data).unwrap();
         assert!(
             result.score > 0.5,
-            format!("score must meet threshold; was {}", result.score)
+            "score must meet threshold; was {}",
+            result.score
         );
         assert_eq!(
             result.license.expect("result has a license").name,
@@ -205,6 +833,137 @@ mod tests {
         assert!(result.license.is_none(), "result license is None");
     }
 
+    #[test]
+    fn frequency_fallback_rescues_near_miss() {
+        let store = create_dummy_store();
+        // license-1's three words are all present with exactly the right counts,
+        // but enough extra vocabulary is mixed in that the primary scorer falls
+        // just short of the 0.8 threshold (within the fallback margin).
+        let test_data = TextData::new("aaaaa bbbbb ccccc zzzzz yyyyy");
+
+        // without the fallback enabled, this stays an unconfident near-miss
+        let strategy = ScanStrategy::new(&store).confidence_threshold(0.8);
+        let result = strategy.scan(&test_data).unwrap();
+        assert!(
+            result.license.is_none(),
+            "result has no license without the fallback enabled"
+        );
+
+        // with it enabled, the frequency scorer rescues the match
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.8)
+            .enable_frequency_fallback(true);
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!(
+            result.license.expect("result has a license").name,
+            "license-1"
+        );
+        assert!(
+            result.score > 0.8,
+            "fallback score must meet threshold; was {}",
+            result.score
+        );
+    }
+
+    #[test]
+    fn clarification_override_short_circuits_scan() {
+        let store = create_dummy_store();
+        let vendored = TextData::new("some oddly-formatted vendored file text");
+
+        let mut clarifications = HashMap::new();
+        clarifications.insert(
+            vendored.content_hash(),
+            IdentifiedLicense {
+                name: "BSD-3-Clause".to_string(),
+                kind: LicenseType::Original,
+                match_kind: MatchKind::FullText,
+            },
+        );
+
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .clarifications(clarifications);
+
+        // a clarified hash is pinned immediately, bypassing fuzzy detection
+        let result = strategy.scan(&vendored).unwrap();
+        assert_eq!(result.score, 1.0);
+        assert_eq!(result.confidence, Confidence::Confident);
+        assert_eq!(
+            result.license.expect("result has a license").name,
+            "BSD-3-Clause"
+        );
+        assert!(
+            result.containing.is_empty(),
+            "clarified result skips bounds optimization"
+        );
+
+        // text that isn't clarified still goes through normal analysis
+        let test_data = TextData::new("lorem ipsum\naaaaa bbbbb\nccccc\nhello");
+        let result = strategy.scan(&test_data).unwrap();
+        assert_eq!(
+            result.license.expect("result has a license").name,
+            "license-1"
+        );
+    }
+
+    #[test]
+    fn spdx_expression_ignores_header_and_notice_matches() {
+        let full_text = ContainedResult {
+            score: 1.0,
+            license: IdentifiedLicense {
+                name: "Apache-2.0".to_string(),
+                kind: LicenseType::Original,
+                match_kind: MatchKind::FullText,
+            },
+            line_range: (0, 1),
+            token_range: (0, 1),
+            byte_range: (0, 1),
+            attribution: None,
+            confidence: Confidence::Confident,
+        };
+        let header_only = ContainedResult {
+            license: IdentifiedLicense {
+                name: "MIT".to_string(),
+                match_kind: MatchKind::Header,
+                ..full_text.license.clone()
+            },
+            ..full_text.clone()
+        };
+
+        let result = ScanResult {
+            score: 1.0,
+            license: None,
+            containing: vec![full_text, header_only],
+            attribution: None,
+            confidence: Confidence::Confident,
+        };
+
+        assert_eq!(result.spdx_expression(), Some("Apache-2.0".to_string()));
+    }
+
+    #[test]
+    fn byte_range_does_not_exceed_document_length() {
+        let mut store = Store::new();
+        store.add_license("license-z".into(), "aaaa bbbb cccc dddd".into());
+
+        // no trailing newline after the last line of the document
+        let document = "zzzz yyyy\naaaa bbbb cccc dddd";
+        let test_data = TextData::new(document);
+
+        let strategy = ScanStrategy::new(&store)
+            .confidence_threshold(0.5)
+            .optimize(true)
+            .shallow_limit(1.0);
+        let result = strategy.scan(&test_data).unwrap();
+        let contained = &result.containing[0];
+        assert!(
+            contained.byte_range.1 <= document.len(),
+            "byte_range {:?} exceeds document length {}",
+            contained.byte_range,
+            document.len()
+        );
+    }
+
     #[test]
     fn single_optimize() {
         let store = create_dummy_store();
@@ -249,7 +1008,7 @@ mod tests {
         // inspect the array and ensure we got both licenses
         let mut found1 = 0;
         let mut found2 = 0;
-        for (_, ref contained) in result.containing.iter().enumerate() {
+        for contained in result.containing.iter() {
             match contained.license.name.as_ref() {
                 "license-1" => {
                     assert!(contained.score > 0.5, "license-1 score meets threshold");